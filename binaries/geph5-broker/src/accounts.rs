@@ -0,0 +1,140 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use geph5_broker_protocol::{AccountLevel, GenericError};
+use once_cell::sync::Lazy;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+
+use crate::CONFIG_FILE;
+
+/// A user record as stored behind the `BrokerStore`, keyed by a numeric id
+/// so it can be reused as the subject of an auth token the same way
+/// `Credential::TestDummy` already is.
+#[derive(Clone, Debug)]
+pub struct UserRecord {
+    pub id: i64,
+    pub username: String,
+    pub password_hash: String,
+    pub level: AccountLevel,
+    pub expiry: i64,
+}
+
+/// The subset of `UserRecord` that is safe to hand back over `list_users`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub id: i64,
+    pub username: String,
+    pub level: AccountLevel,
+    pub expiry: i64,
+}
+
+impl From<&UserRecord> for UserInfo {
+    fn from(user: &UserRecord) -> Self {
+        Self {
+            id: user.id,
+            username: user.username.clone(),
+            level: user.level,
+            expiry: user.expiry,
+        }
+    }
+}
+
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return false;
+    };
+    Argon2::default()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_ok()
+}
+
+/// An Argon2 hash of a fixed, unguessed-by-callers password, used only to
+/// give `verify_user_credential` something to hash against when the
+/// username doesn't exist.
+static DUMMY_PASSWORD_HASH: Lazy<String> = Lazy::new(|| {
+    hash_password("geph5-broker-dummy-hash-for-unknown-usernames")
+        .expect("hashing a fixed password cannot fail")
+});
+
+/// Verifies a username/password login attempt, returning the user's id on
+/// success. Runs an Argon2 verify against `DUMMY_PASSWORD_HASH` when `user`
+/// is `None`, so that an unknown username costs the same as a known one
+/// with the wrong password -- otherwise `get_auth_token` would let an
+/// attacker enumerate valid usernames by timing.
+pub fn verify_user_credential(user: Option<&UserRecord>, password: &str) -> Option<i64> {
+    let hash = user
+        .map(|user| user.password_hash.as_str())
+        .unwrap_or(DUMMY_PASSWORD_HASH.as_str());
+    if verify_password(password, hash) {
+        user.map(|user| user.id)
+    } else {
+        None
+    }
+}
+
+/// Checks an admin token against the one configured in `CONFIG_FILE`. Both
+/// sides are hashed first so the comparison is fixed-length, then compared
+/// in constant time, the same way `insert_exit`/`insert_bridge` keep their
+/// MAC checks off naive string equality.
+pub fn check_admin_token(admin_token: &str) -> Result<(), GenericError> {
+    let expected = blake3::hash(CONFIG_FILE.wait().admin_token.as_bytes());
+    let given = blake3::hash(admin_token.as_bytes());
+    if constant_time_eq(expected.as_bytes(), given.as_bytes()) {
+        Ok(())
+    } else {
+        Err(GenericError("invalid admin token".into()))
+    }
+}
+
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    let mut diff = 0u8;
+    for i in 0..32 {
+        diff |= a[i] ^ b[i];
+    }
+    diff == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_with_password(password: &str) -> UserRecord {
+        UserRecord {
+            id: 7,
+            username: "alice".into(),
+            password_hash: hash_password(password).unwrap(),
+            level: AccountLevel::Free,
+            expiry: 0,
+        }
+    }
+
+    #[test]
+    fn verify_user_credential_accepts_correct_password() {
+        let user = user_with_password("hunter2");
+        assert_eq!(
+            verify_user_credential(Some(&user), "hunter2"),
+            Some(user.id)
+        );
+    }
+
+    #[test]
+    fn verify_user_credential_rejects_wrong_password() {
+        let user = user_with_password("hunter2");
+        assert_eq!(verify_user_credential(Some(&user), "wrong"), None);
+    }
+
+    #[test]
+    fn verify_user_credential_rejects_unknown_username() {
+        assert_eq!(verify_user_credential(None, "whatever"), None);
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use moka::future::Cache;
+
+pub use geph5_histogram::LatencyHistogram;
+
+/// Per-bridge latency histograms on a rolling window: entries naturally
+/// expire and restart from zero, so a bridge that was briefly slow isn't
+/// penalized forever.
+pub struct BridgeHistograms {
+    inner: Cache<String, std::sync::Arc<LatencyHistogram>>,
+}
+
+impl BridgeHistograms {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            inner: Cache::builder().time_to_live(window).build(),
+        }
+    }
+
+    pub async fn record(&self, bridge_key: &str, latency_ms: f64) {
+        let hist = self
+            .inner
+            .get_with(bridge_key.to_string(), async {
+                std::sync::Arc::new(LatencyHistogram::default())
+            })
+            .await;
+        hist.record(latency_ms);
+    }
+
+    /// Returns `(p50, p90)` in milliseconds for a bridge, or `None` if we
+    /// have no samples for it yet.
+    pub async fn percentiles(&self, bridge_key: &str) -> Option<(f64, f64)> {
+        let hist = self.inner.get(bridge_key).await?;
+        Some((hist.percentile(0.5)?, hist.percentile(0.9)?))
+    }
+
+    /// Merges every bridge's histogram into one and returns its `(p50,
+    /// p90)`, for broker-wide metrics rather than per-bridge routing.
+    pub fn aggregate_percentiles(&self) -> Option<(f64, f64)> {
+        let merged = LatencyHistogram::default();
+        let mut any = false;
+        for (_, hist) in self.inner.iter() {
+            merged.merge(&hist);
+            any = true;
+        }
+        if !any {
+            return None;
+        }
+        Some((merged.percentile(0.5)?, merged.percentile(0.9)?))
+    }
+}
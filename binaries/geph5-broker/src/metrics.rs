@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use geph5_broker_protocol::AccountLevel;
+use serde::{Deserialize, Serialize};
+
+/// Aggregate broker counters, kept as plain atomics so the hot paths that
+/// bump them (issuing tokens, probing bridges) never have to take a lock.
+#[derive(Default)]
+pub struct BrokerMetrics {
+    connect_tokens_free: AtomicU64,
+    connect_tokens_plus: AtomicU64,
+    bridge_successes: AtomicU64,
+    bridge_failures: AtomicU64,
+}
+
+impl BrokerMetrics {
+    pub fn record_connect_token(&self, level: AccountLevel) {
+        match level {
+            AccountLevel::Free => self.connect_tokens_free.fetch_add(1, Ordering::Relaxed),
+            AccountLevel::Plus => self.connect_tokens_plus.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub fn record_bridge_success(&self) {
+        self.bridge_successes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_bridge_failure(&self) {
+        self.bridge_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns `(connect_tokens_free, connect_tokens_plus, bridge_successes,
+    /// bridge_failures)`.
+    pub fn counts(&self) -> (u64, u64, u64, u64) {
+        (
+            self.connect_tokens_free.load(Ordering::Relaxed),
+            self.connect_tokens_plus.load(Ordering::Relaxed),
+            self.bridge_successes.load(Ordering::Relaxed),
+            self.bridge_failures.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// A cheap-to-serialize snapshot of broker health, scraped by operators in
+/// place of grepping `tracing::warn!` lines.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub connect_tokens_free: u64,
+    pub connect_tokens_plus: u64,
+    pub exit_load_total: f32,
+    pub bridge_successes: u64,
+    pub bridge_failures: u64,
+    pub route_latency_p50_ms: f64,
+    pub route_latency_p90_ms: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_start_at_zero() {
+        let metrics = BrokerMetrics::default();
+        assert_eq!(metrics.counts(), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn record_connect_token_splits_by_level() {
+        let metrics = BrokerMetrics::default();
+        metrics.record_connect_token(AccountLevel::Free);
+        metrics.record_connect_token(AccountLevel::Free);
+        metrics.record_connect_token(AccountLevel::Plus);
+        assert_eq!(metrics.counts(), (2, 1, 0, 0));
+    }
+
+    #[test]
+    fn record_bridge_success_and_failure_are_independent() {
+        let metrics = BrokerMetrics::default();
+        metrics.record_bridge_success();
+        metrics.record_bridge_success();
+        metrics.record_bridge_failure();
+        assert_eq!(metrics.counts(), (0, 0, 2, 1));
+    }
+}
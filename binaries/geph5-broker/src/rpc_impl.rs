@@ -1,4 +1,9 @@
-use std::{net::SocketAddr, ops::Deref, sync::Arc, time::Duration};
+use std::{
+    net::SocketAddr,
+    ops::Deref,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -14,13 +19,31 @@ use once_cell::sync::Lazy;
 use rand::Rng as _;
 
 use crate::{
-    auth_token::{self, new_auth_token, valid_auth_token},
-    database::{insert_exit, query_bridges, ExitRow, POSTGRES},
+    accounts::{check_admin_token, hash_password, verify_user_credential, UserInfo},
+    histogram::BridgeHistograms,
+    metrics::{BrokerMetrics, MetricsSnapshot},
     routes::bridge_to_leaf_route,
+    store::{BrokerStore, ExitRow},
     CONFIG_FILE, FREE_MIZARU_SK, MASTER_SECRET, PLUS_MIZARU_SK,
 };
 
-pub struct BrokerImpl {}
+/// How many of the fastest-ranked bridges we hand a client to race.
+const RACE_CANDIDATES: usize = 5;
+/// Bridges with a p90 RTT above this are demoted out of the race entirely.
+const P90_LIMIT_MS: f64 = 1500.0;
+/// A penalty latency recorded for a bridge that didn't answer at all, so
+/// that an unreachable bridge's histogram still reflects the outage.
+const TIMEOUT_PENALTY_MS: f64 = 5000.0;
+
+pub struct BrokerImpl {
+    pub store: Arc<dyn BrokerStore>,
+    pub bridge_histograms: BridgeHistograms,
+    pub metrics: Arc<BrokerMetrics>,
+}
+
+fn bridge_key(desc: &BridgeDescriptor) -> String {
+    desc.control_listen.to_string()
+}
 
 #[async_trait]
 impl BrokerProtocol for BrokerImpl {
@@ -40,15 +63,94 @@ impl BrokerProtocol for BrokerImpl {
     async fn get_auth_token(&self, credential: Credential) -> Result<String, AuthError> {
         let user_id = match credential {
             Credential::TestDummy => 42, // User ID for TestDummy
+            Credential::UsernamePassword { username, password } => {
+                let user = self
+                    .store
+                    .get_user_by_username(&username)
+                    .await
+                    .map_err(|_| AuthError::RateLimited)?;
+                verify_user_credential(user.as_ref(), &password).ok_or(AuthError::Forbidden)?
+            }
         };
 
-        let token = new_auth_token(user_id)
+        let token = self
+            .store
+            .create_auth_token(user_id)
             .await
             .map_err(|_| AuthError::RateLimited)?;
 
         Ok(token)
     }
 
+    async fn create_user(
+        &self,
+        admin_token: String,
+        username: String,
+        password: String,
+        level: AccountLevel,
+        expiry: i64,
+    ) -> Result<(), GenericError> {
+        check_admin_token(&admin_token)?;
+        let password_hash = hash_password(&password)?;
+        self.store
+            .create_user(&username, &password_hash, level, expiry)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_users(&self, admin_token: String) -> Result<Vec<UserInfo>, GenericError> {
+        check_admin_token(&admin_token)?;
+        Ok(self
+            .store
+            .list_users()
+            .await?
+            .iter()
+            .map(UserInfo::from)
+            .collect())
+    }
+
+    async fn delete_user(&self, admin_token: String, user_id: i64) -> Result<(), GenericError> {
+        check_admin_token(&admin_token)?;
+        self.store.delete_user(user_id).await?;
+        Ok(())
+    }
+
+    async fn get_metrics(&self) -> Result<MetricsSnapshot, GenericError> {
+        static METRICS_CACHE: Lazy<Cache<(), MetricsSnapshot>> = Lazy::new(|| {
+            Cache::builder()
+                .time_to_live(Duration::from_secs(5))
+                .build()
+        });
+
+        METRICS_CACHE
+            .try_get_with((), async {
+                let exit_load_total = self
+                    .store
+                    .list_exits()
+                    .await?
+                    .iter()
+                    .map(|e| e.load)
+                    .sum();
+                let (route_latency_p50_ms, route_latency_p90_ms) = self
+                    .bridge_histograms
+                    .aggregate_percentiles()
+                    .unwrap_or((0.0, 0.0));
+                let (connect_tokens_free, connect_tokens_plus, bridge_successes, bridge_failures) =
+                    self.metrics.counts();
+                anyhow::Ok(MetricsSnapshot {
+                    connect_tokens_free,
+                    connect_tokens_plus,
+                    exit_load_total,
+                    bridge_successes,
+                    bridge_failures,
+                    route_latency_p50_ms,
+                    route_latency_p90_ms,
+                })
+            })
+            .await
+            .map_err(|e: Arc<anyhow::Error>| GenericError(e.to_string()))
+    }
+
     async fn get_connect_token(
         &self,
         auth_token: String,
@@ -56,17 +158,34 @@ impl BrokerProtocol for BrokerImpl {
         epoch: u16,
         blind_token: BlindedClientToken,
     ) -> Result<BlindedSignature, AuthError> {
-        match valid_auth_token(&auth_token).await {
-            Ok(auth) => {
-                if !auth {
-                    return Err(AuthError::Forbidden);
-                }
-            }
+        let user_id = match self.store.resolve_auth_token(&auth_token).await {
+            Ok(Some(user_id)) => user_id,
+            Ok(None) => return Err(AuthError::Forbidden),
             Err(err) => {
                 tracing::warn!(err = debug(err), "database failed");
                 return Err(AuthError::RateLimited);
             }
+        };
+
+        if matches!(level, AccountLevel::Plus) {
+            let user = match self.store.get_user_by_id(user_id).await {
+                Ok(user) => user,
+                Err(err) => {
+                    tracing::warn!(err = debug(err), "database failed");
+                    return Err(AuthError::RateLimited);
+                }
+            };
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64;
+            match user {
+                Some(user) if matches!(user.level, AccountLevel::Plus) && user.expiry > now => {}
+                _ => return Err(AuthError::Forbidden),
+            }
         }
+
+        self.metrics.record_connect_token(level);
         Ok(match level {
             AccountLevel::Free => &FREE_MIZARU_SK,
             AccountLevel::Plus => &PLUS_MIZARU_SK,
@@ -83,26 +202,25 @@ impl BrokerProtocol for BrokerImpl {
 
         EXIT_CACHE
             .try_get_with((), async {
-                let exits: Vec<(VerifyingKey, ExitDescriptor)> =
-                    sqlx::query_as("select * from exits_new")
-                        .fetch_all(POSTGRES.deref())
-                        .await?
-                        .into_iter()
-                        .map(|row: ExitRow| {
-                            (
-                                VerifyingKey::from_bytes(&row.pubkey).unwrap(),
-                                ExitDescriptor {
-                                    c2e_listen: row.c2e_listen.parse().unwrap(),
-                                    b2e_listen: row.b2e_listen.parse().unwrap(),
-                                    country: CountryCode::for_alpha2_caseless(&row.country)
-                                        .unwrap(),
-                                    city: row.city,
-                                    load: row.load,
-                                    expiry: row.expiry as _,
-                                },
-                            )
-                        })
-                        .collect();
+                let exits: Vec<(VerifyingKey, ExitDescriptor)> = self
+                    .store
+                    .list_exits()
+                    .await?
+                    .into_iter()
+                    .map(|row: ExitRow| {
+                        (
+                            VerifyingKey::from_bytes(&row.pubkey).unwrap(),
+                            ExitDescriptor {
+                                c2e_listen: row.c2e_listen.parse().unwrap(),
+                                b2e_listen: row.b2e_listen.parse().unwrap(),
+                                country: CountryCode::for_alpha2_caseless(&row.country).unwrap(),
+                                city: row.city,
+                                load: row.load,
+                                expiry: row.expiry as _,
+                            },
+                        )
+                    })
+                    .collect();
                 let exit_list = ExitList {
                     all_exits: exits,
                     city_names: serde_yaml::from_str(include_str!("city_names.yaml")).unwrap(),
@@ -142,12 +260,23 @@ impl BrokerProtocol for BrokerImpl {
 
         // TODO filter out plus only
 
-        let raw_descriptors = query_bridges(&format!("{:?}", token)).await?;
-        let mut routes = vec![];
+        let raw_descriptors = self.store.query_bridges(&format!("{:?}", token)).await?;
+        let mut candidates = vec![];
         for desc in raw_descriptors {
+            let key = bridge_key(&desc);
+            let started = Instant::now();
             match bridge_to_leaf_route(&desc, exit).await {
-                Ok(route) => routes.push(route),
+                Ok(route) => {
+                    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+                    self.bridge_histograms.record(&key, latency_ms).await;
+                    self.metrics.record_bridge_success();
+                    candidates.push((key, route));
+                }
                 Err(err) => {
+                    self.bridge_histograms
+                        .record(&key, TIMEOUT_PENALTY_MS)
+                        .await;
+                    self.metrics.record_bridge_failure();
                     tracing::warn!(
                         err = debug(err),
                         bridge = debug(desc),
@@ -156,6 +285,24 @@ impl BrokerProtocol for BrokerImpl {
                 }
             }
         }
+
+        let mut ranked = vec![];
+        for (key, route) in candidates {
+            let Some((p50, p90)) = self.bridge_histograms.percentiles(&key).await else {
+                ranked.push((0.0, route));
+                continue;
+            };
+            if p90 > P90_LIMIT_MS {
+                continue;
+            }
+            ranked.push((p50, route));
+        }
+        ranked.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let routes = ranked
+            .into_iter()
+            .take(RACE_CANDIDATES)
+            .map(|(_, route)| route)
+            .collect();
         Ok(RouteDescriptor::Race(routes))
     }
 
@@ -168,7 +315,7 @@ impl BrokerProtocol for BrokerImpl {
         let pubkey = descriptor.pubkey;
         let descriptor = descriptor.verify(DOMAIN_EXIT_DESCRIPTOR, |_| true)?;
         let exit = ExitRow {
-            pubkey: pubkey.to_bytes(),
+            pubkey: pubkey.to_bytes().to_vec(),
             c2e_listen: descriptor.c2e_listen.to_string(),
             b2e_listen: descriptor.b2e_listen.to_string(),
             country: descriptor.country.alpha2().into(),
@@ -176,7 +323,7 @@ impl BrokerProtocol for BrokerImpl {
             load: descriptor.load,
             expiry: descriptor.expiry as _,
         };
-        insert_exit(&exit).await?;
+        self.store.upsert_exit(&exit).await?;
         Ok(())
     }
 
@@ -184,20 +331,7 @@ impl BrokerProtocol for BrokerImpl {
         let descriptor = descriptor
             .verify(blake3::hash(CONFIG_FILE.wait().bridge_token.as_bytes()).as_bytes())?;
 
-        sqlx::query(
-            r#"
-            INSERT INTO bridges_new (listen, cookie, pool, expiry)
-            VALUES ($1, $2, $3, $4)
-            ON CONFLICT (listen) DO UPDATE
-            SET cookie = $2, pool = $3, expiry = $4
-            "#,
-        )
-        .bind(descriptor.control_listen.to_string())
-        .bind(descriptor.control_cookie.to_string())
-        .bind(descriptor.pool.to_string())
-        .bind(descriptor.expiry as i64)
-        .execute(&*POSTGRES)
-        .await?;
+        self.store.upsert_bridge(&descriptor).await?;
         Ok(())
     }
 }
@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use geph5_broker_protocol::{AccountLevel, BridgeDescriptor};
+
+use crate::accounts::UserRecord;
+
+mod postgres;
+mod sqlite;
+
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+/// A row in the `exits_new` table, decoupled from whichever SQL backend is
+/// actually storing it.
+#[derive(Clone, Debug)]
+pub struct ExitRow {
+    pub pubkey: Vec<u8>,
+    pub c2e_listen: String,
+    pub b2e_listen: String,
+    pub country: String,
+    pub city: String,
+    pub load: f32,
+    pub expiry: i64,
+}
+
+/// Everything `BrokerImpl` needs from persistent storage. Implemented once
+/// for Postgres (for large, multi-node deployments) and once for embedded
+/// SQLite (so a small operator can run the broker as a single binary with
+/// no external database server).
+#[async_trait]
+pub trait BrokerStore: Send + Sync + 'static {
+    async fn upsert_exit(&self, exit: &ExitRow) -> anyhow::Result<()>;
+
+    async fn list_exits(&self) -> anyhow::Result<Vec<ExitRow>>;
+
+    async fn upsert_bridge(&self, bridge: &BridgeDescriptor) -> anyhow::Result<()>;
+
+    async fn query_bridges(&self, token: &str) -> anyhow::Result<Vec<BridgeDescriptor>>;
+
+    async fn create_auth_token(&self, user_id: i64) -> anyhow::Result<String>;
+
+    /// Resolves an auth token to the user id it was issued for, or `None`
+    /// if the token is unknown. Tokens do not expire.
+    async fn resolve_auth_token(&self, token: &str) -> anyhow::Result<Option<i64>>;
+
+    async fn create_user(
+        &self,
+        username: &str,
+        password_hash: &str,
+        level: AccountLevel,
+        expiry: i64,
+    ) -> anyhow::Result<i64>;
+
+    async fn list_users(&self) -> anyhow::Result<Vec<UserRecord>>;
+
+    async fn delete_user(&self, id: i64) -> anyhow::Result<()>;
+
+    async fn get_user_by_username(&self, username: &str) -> anyhow::Result<Option<UserRecord>>;
+
+    async fn get_user_by_id(&self, id: i64) -> anyhow::Result<Option<UserRecord>>;
+}
+
+fn account_level_to_str(level: AccountLevel) -> &'static str {
+    match level {
+        AccountLevel::Free => "free",
+        AccountLevel::Plus => "plus",
+    }
+}
+
+fn account_level_from_str(s: &str) -> AccountLevel {
+    match s {
+        "plus" => AccountLevel::Plus,
+        _ => AccountLevel::Free,
+    }
+}
+
+/// Picks a backend based on `CONFIG_FILE`: a `sqlite_path` selects the
+/// embedded backend, otherwise the `postgres_url` is used.
+pub async fn open_from_config(
+    config: &crate::Config,
+) -> anyhow::Result<std::sync::Arc<dyn BrokerStore>> {
+    if let Some(path) = &config.sqlite_path {
+        Ok(std::sync::Arc::new(SqliteStore::connect(path).await?))
+    } else {
+        Ok(std::sync::Arc::new(
+            PostgresStore::connect(&config.postgres_url).await?,
+        ))
+    }
+}
@@ -0,0 +1,250 @@
+use async_trait::async_trait;
+use geph5_broker_protocol::{AccountLevel, BridgeDescriptor};
+use rand::Rng as _;
+use sqlx::PgPool;
+
+use crate::accounts::UserRecord;
+
+use super::{account_level_from_str, account_level_to_str, BrokerStore, ExitRow};
+
+/// The original backend: a shared Postgres cluster, as used by large
+/// deployments.
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let pool = PgPool::connect(url).await?;
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl BrokerStore for PostgresStore {
+    async fn upsert_exit(&self, exit: &ExitRow) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO exits_new (pubkey, c2e_listen, b2e_listen, country, city, load, expiry)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (pubkey) DO UPDATE
+            SET c2e_listen = $2, b2e_listen = $3, country = $4, city = $5, load = $6, expiry = $7
+            "#,
+        )
+        .bind(&exit.pubkey)
+        .bind(&exit.c2e_listen)
+        .bind(&exit.b2e_listen)
+        .bind(&exit.country)
+        .bind(&exit.city)
+        .bind(exit.load)
+        .bind(exit.expiry)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_exits(&self) -> anyhow::Result<Vec<ExitRow>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            pubkey: Vec<u8>,
+            c2e_listen: String,
+            b2e_listen: String,
+            country: String,
+            city: String,
+            load: f32,
+            expiry: i64,
+        }
+
+        let rows: Vec<Row> = sqlx::query_as("select * from exits_new")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ExitRow {
+                pubkey: row.pubkey,
+                c2e_listen: row.c2e_listen,
+                b2e_listen: row.b2e_listen,
+                country: row.country,
+                city: row.city,
+                load: row.load,
+                expiry: row.expiry,
+            })
+            .collect())
+    }
+
+    async fn upsert_bridge(&self, bridge: &BridgeDescriptor) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO bridges_new (listen, cookie, pool, expiry)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (listen) DO UPDATE
+            SET cookie = $2, pool = $3, expiry = $4
+            "#,
+        )
+        .bind(bridge.control_listen.to_string())
+        .bind(bridge.control_cookie.to_string())
+        .bind(bridge.pool.to_string())
+        .bind(bridge.expiry as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn query_bridges(&self, token: &str) -> anyhow::Result<Vec<BridgeDescriptor>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            listen: String,
+            cookie: String,
+            pool: String,
+            expiry: i64,
+        }
+
+        let rows: Vec<Row> = sqlx::query_as(
+            "select listen, cookie, pool, expiry from bridges_new where pool = $1 or pool = 'all'",
+        )
+        .bind(token)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| BridgeDescriptor {
+                control_listen: row.listen.parse().unwrap(),
+                control_cookie: row.cookie,
+                pool: row.pool,
+                expiry: row.expiry as _,
+            })
+            .collect())
+    }
+
+    async fn create_auth_token(&self, user_id: i64) -> anyhow::Result<String> {
+        let token: String = rand::thread_rng()
+            .sample_iter(rand::distributions::Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        sqlx::query(
+            "INSERT INTO auth_tokens (token, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(&token)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    async fn resolve_auth_token(&self, token: &str) -> anyhow::Result<Option<i64>> {
+        let found: Option<(i64,)> = sqlx::query_as("select user_id from auth_tokens where token = $1")
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(found.map(|(user_id,)| user_id))
+    }
+
+    async fn create_user(
+        &self,
+        username: &str,
+        password_hash: &str,
+        level: AccountLevel,
+        expiry: i64,
+    ) -> anyhow::Result<i64> {
+        let (id,): (i64,) = sqlx::query_as(
+            r#"
+            INSERT INTO users (username, password_hash, level, expiry)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(username)
+        .bind(password_hash)
+        .bind(account_level_to_str(level))
+        .bind(expiry)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn list_users(&self) -> anyhow::Result<Vec<UserRecord>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            id: i64,
+            username: String,
+            password_hash: String,
+            level: String,
+            expiry: i64,
+        }
+
+        let rows: Vec<Row> = sqlx::query_as("select * from users")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| UserRecord {
+                id: row.id,
+                username: row.username,
+                password_hash: row.password_hash,
+                level: account_level_from_str(&row.level),
+                expiry: row.expiry,
+            })
+            .collect())
+    }
+
+    async fn delete_user(&self, id: i64) -> anyhow::Result<()> {
+        let mut txn = self.pool.begin().await?;
+        sqlx::query("delete from auth_tokens where user_id = $1")
+            .bind(id)
+            .execute(&mut *txn)
+            .await?;
+        sqlx::query("delete from users where id = $1")
+            .bind(id)
+            .execute(&mut *txn)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> anyhow::Result<Option<UserRecord>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            id: i64,
+            username: String,
+            password_hash: String,
+            level: String,
+            expiry: i64,
+        }
+
+        let row: Option<Row> = sqlx::query_as("select * from users where username = $1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| UserRecord {
+            id: row.id,
+            username: row.username,
+            password_hash: row.password_hash,
+            level: account_level_from_str(&row.level),
+            expiry: row.expiry,
+        }))
+    }
+
+    async fn get_user_by_id(&self, id: i64) -> anyhow::Result<Option<UserRecord>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            id: i64,
+            username: String,
+            password_hash: String,
+            level: String,
+            expiry: i64,
+        }
+
+        let row: Option<Row> = sqlx::query_as("select * from users where id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| UserRecord {
+            id: row.id,
+            username: row.username,
+            password_hash: row.password_hash,
+            level: account_level_from_str(&row.level),
+            expiry: row.expiry,
+        }))
+    }
+}
@@ -0,0 +1,418 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use geph5_broker_protocol::{AccountLevel, BridgeDescriptor};
+use rand::Rng as _;
+use sqlx::SqlitePool;
+
+use crate::accounts::UserRecord;
+
+use super::{account_level_from_str, account_level_to_str, BrokerStore, ExitRow};
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS exits_new (
+    pubkey BLOB PRIMARY KEY,
+    c2e_listen TEXT NOT NULL,
+    b2e_listen TEXT NOT NULL,
+    country TEXT NOT NULL,
+    city TEXT NOT NULL,
+    load REAL NOT NULL,
+    expiry INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS bridges_new (
+    listen TEXT PRIMARY KEY,
+    cookie TEXT NOT NULL,
+    pool TEXT NOT NULL,
+    expiry INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS auth_tokens (
+    token TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    level TEXT NOT NULL,
+    expiry INTEGER NOT NULL
+);
+"#;
+
+/// An embedded backend so that a small operator can run the broker as a
+/// single binary with no external database server to administer.
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub async fn connect(path: &Path) -> anyhow::Result<Self> {
+        let pool = SqlitePool::connect(&format!("sqlite://{}?mode=rwc", path.display())).await?;
+        sqlx::query(SCHEMA).execute(&pool).await?;
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl BrokerStore for SqliteStore {
+    async fn upsert_exit(&self, exit: &ExitRow) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO exits_new (pubkey, c2e_listen, b2e_listen, country, city, load, expiry)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT (pubkey) DO UPDATE
+            SET c2e_listen = ?2, b2e_listen = ?3, country = ?4, city = ?5, load = ?6, expiry = ?7
+            "#,
+        )
+        .bind(&exit.pubkey)
+        .bind(&exit.c2e_listen)
+        .bind(&exit.b2e_listen)
+        .bind(&exit.country)
+        .bind(&exit.city)
+        .bind(exit.load)
+        .bind(exit.expiry)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_exits(&self) -> anyhow::Result<Vec<ExitRow>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            pubkey: Vec<u8>,
+            c2e_listen: String,
+            b2e_listen: String,
+            country: String,
+            city: String,
+            load: f32,
+            expiry: i64,
+        }
+
+        let rows: Vec<Row> = sqlx::query_as("select * from exits_new")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ExitRow {
+                pubkey: row.pubkey,
+                c2e_listen: row.c2e_listen,
+                b2e_listen: row.b2e_listen,
+                country: row.country,
+                city: row.city,
+                load: row.load,
+                expiry: row.expiry,
+            })
+            .collect())
+    }
+
+    async fn upsert_bridge(&self, bridge: &BridgeDescriptor) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO bridges_new (listen, cookie, pool, expiry)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (listen) DO UPDATE
+            SET cookie = ?2, pool = ?3, expiry = ?4
+            "#,
+        )
+        .bind(bridge.control_listen.to_string())
+        .bind(bridge.control_cookie.to_string())
+        .bind(bridge.pool.to_string())
+        .bind(bridge.expiry as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn query_bridges(&self, token: &str) -> anyhow::Result<Vec<BridgeDescriptor>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            listen: String,
+            cookie: String,
+            pool: String,
+            expiry: i64,
+        }
+
+        let rows: Vec<Row> = sqlx::query_as(
+            "select listen, cookie, pool, expiry from bridges_new where pool = ?1 or pool = 'all'",
+        )
+        .bind(token)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| BridgeDescriptor {
+                control_listen: row.listen.parse().unwrap(),
+                control_cookie: row.cookie,
+                pool: row.pool,
+                expiry: row.expiry as _,
+            })
+            .collect())
+    }
+
+    async fn create_auth_token(&self, user_id: i64) -> anyhow::Result<String> {
+        let token: String = rand::thread_rng()
+            .sample_iter(rand::distributions::Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        sqlx::query("INSERT OR IGNORE INTO auth_tokens (token, user_id) VALUES (?1, ?2)")
+            .bind(&token)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(token)
+    }
+
+    async fn resolve_auth_token(&self, token: &str) -> anyhow::Result<Option<i64>> {
+        let found: Option<(i64,)> =
+            sqlx::query_as("select user_id from auth_tokens where token = ?1")
+                .bind(token)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(found.map(|(user_id,)| user_id))
+    }
+
+    async fn create_user(
+        &self,
+        username: &str,
+        password_hash: &str,
+        level: AccountLevel,
+        expiry: i64,
+    ) -> anyhow::Result<i64> {
+        sqlx::query("INSERT INTO users (username, password_hash, level, expiry) VALUES (?1, ?2, ?3, ?4)")
+            .bind(username)
+            .bind(password_hash)
+            .bind(account_level_to_str(level))
+            .bind(expiry)
+            .execute(&self.pool)
+            .await?;
+        let (id,): (i64,) = sqlx::query_as("select id from users where username = ?1")
+            .bind(username)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    async fn list_users(&self) -> anyhow::Result<Vec<UserRecord>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            id: i64,
+            username: String,
+            password_hash: String,
+            level: String,
+            expiry: i64,
+        }
+
+        let rows: Vec<Row> = sqlx::query_as("select * from users")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| UserRecord {
+                id: row.id,
+                username: row.username,
+                password_hash: row.password_hash,
+                level: account_level_from_str(&row.level),
+                expiry: row.expiry,
+            })
+            .collect())
+    }
+
+    async fn delete_user(&self, id: i64) -> anyhow::Result<()> {
+        let mut txn = self.pool.begin().await?;
+        sqlx::query("delete from auth_tokens where user_id = ?1")
+            .bind(id)
+            .execute(&mut *txn)
+            .await?;
+        sqlx::query("delete from users where id = ?1")
+            .bind(id)
+            .execute(&mut *txn)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> anyhow::Result<Option<UserRecord>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            id: i64,
+            username: String,
+            password_hash: String,
+            level: String,
+            expiry: i64,
+        }
+
+        let row: Option<Row> = sqlx::query_as("select * from users where username = ?1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| UserRecord {
+            id: row.id,
+            username: row.username,
+            password_hash: row.password_hash,
+            level: account_level_from_str(&row.level),
+            expiry: row.expiry,
+        }))
+    }
+
+    async fn get_user_by_id(&self, id: i64) -> anyhow::Result<Option<UserRecord>> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            id: i64,
+            username: String,
+            password_hash: String,
+            level: String,
+            expiry: i64,
+        }
+
+        let row: Option<Row> = sqlx::query_as("select * from users where id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| UserRecord {
+            id: row.id,
+            username: row.username,
+            password_hash: row.password_hash,
+            level: account_level_from_str(&row.level),
+            expiry: row.expiry,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use geph5_broker_protocol::AccountLevel;
+
+    use super::*;
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    async fn open_temp_store() -> SqliteStore {
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "geph5-broker-store-test-{}-{id}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        SqliteStore::connect(&path).await.unwrap()
+    }
+
+    fn sample_exit() -> ExitRow {
+        ExitRow {
+            pubkey: vec![1, 2, 3],
+            c2e_listen: "1.2.3.4:1000".into(),
+            b2e_listen: "1.2.3.4:1001".into(),
+            country: "us".into(),
+            city: "nyc".into(),
+            load: 0.5,
+            expiry: 1000,
+        }
+    }
+
+    #[tokio::test]
+    async fn upsert_exit_then_list_roundtrips() {
+        let store = open_temp_store().await;
+        store.upsert_exit(&sample_exit()).await.unwrap();
+        let exits = store.list_exits().await.unwrap();
+        assert_eq!(exits.len(), 1);
+        assert_eq!(exits[0].pubkey, vec![1, 2, 3]);
+
+        let mut updated = sample_exit();
+        updated.load = 0.9;
+        store.upsert_exit(&updated).await.unwrap();
+        let exits = store.list_exits().await.unwrap();
+        assert_eq!(exits.len(), 1);
+        assert_eq!(exits[0].load, 0.9);
+    }
+
+    #[tokio::test]
+    async fn upsert_bridge_then_query_roundtrips() {
+        let store = open_temp_store().await;
+        let bridge = BridgeDescriptor {
+            control_listen: "1.2.3.4:2000".parse().unwrap(),
+            control_cookie: "cookie".into(),
+            pool: "all".into(),
+            expiry: 1000,
+        };
+        store.upsert_bridge(&bridge).await.unwrap();
+
+        let found = store.query_bridges("some-token").await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].control_cookie, "cookie");
+    }
+
+    #[tokio::test]
+    async fn create_and_resolve_auth_token() {
+        let store = open_temp_store().await;
+        let token = store.create_auth_token(42).await.unwrap();
+        assert_eq!(store.resolve_auth_token(&token).await.unwrap(), Some(42));
+        assert_eq!(
+            store.resolve_auth_token("not-a-real-token").await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn create_user_then_lookup_by_username_and_id() {
+        let store = open_temp_store().await;
+        let id = store
+            .create_user("alice", "hash", AccountLevel::Plus, 1000)
+            .await
+            .unwrap();
+
+        let by_username = store.get_user_by_username("alice").await.unwrap().unwrap();
+        assert_eq!(by_username.id, id);
+        assert!(matches!(by_username.level, AccountLevel::Plus));
+
+        let by_id = store.get_user_by_id(id).await.unwrap().unwrap();
+        assert_eq!(by_id.username, "alice");
+
+        assert!(store
+            .get_user_by_username("bob")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_user_cascades_to_auth_tokens() {
+        let store = open_temp_store().await;
+        let id = store
+            .create_user("carol", "hash", AccountLevel::Free, 1000)
+            .await
+            .unwrap();
+        let token = store.create_auth_token(id).await.unwrap();
+
+        store.delete_user(id).await.unwrap();
+
+        assert!(store.get_user_by_id(id).await.unwrap().is_none());
+        assert_eq!(store.resolve_auth_token(&token).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn list_users_returns_every_created_user() {
+        let store = open_temp_store().await;
+        store
+            .create_user("alice", "hash", AccountLevel::Free, 1000)
+            .await
+            .unwrap();
+        store
+            .create_user("bob", "hash", AccountLevel::Plus, 2000)
+            .await
+            .unwrap();
+
+        let mut usernames: Vec<String> = store
+            .list_users()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|u| u.username)
+            .collect();
+        usernames.sort();
+        assert_eq!(usernames, vec!["alice".to_string(), "bob".to_string()]);
+    }
+}
@@ -0,0 +1,159 @@
+use std::{path::Path, sync::Mutex};
+
+use rusqlite::{Connection, OptionalExtension};
+
+/// A minimal embedded key-value tree, mirroring Conduit's `KvTree`
+/// abstraction: a single file on disk, atomic single- and multi-key
+/// writes, and cheap enumeration for export/import.
+pub trait KvTree: Send + Sync {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    fn insert(&self, key: &str, value: &[u8]) -> anyhow::Result<()>;
+    fn remove(&self, key: &str) -> anyhow::Result<()>;
+    fn keys(&self) -> anyhow::Result<Vec<String>>;
+    fn apply(&self, ops: &[KvOp]) -> anyhow::Result<()>;
+}
+
+/// One operation in an atomic multi-key transaction.
+pub enum KvOp {
+    Insert(String, Vec<u8>),
+    Remove(String),
+}
+
+pub struct SqliteKvTree {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteKvTree {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS kv (key TEXT PRIMARY KEY, value BLOB NOT NULL)",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl KvTree for SqliteKvTree {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row("SELECT value FROM kv WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn insert(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO kv (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = ?2",
+            (key, value),
+        )?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM kv WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    fn keys(&self) -> anyhow::Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT key FROM kv")?;
+        let keys = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(keys)
+    }
+
+    fn apply(&self, ops: &[KvOp]) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let txn = conn.transaction()?;
+        for op in ops {
+            match op {
+                KvOp::Insert(key, value) => {
+                    txn.execute(
+                        "INSERT INTO kv (key, value) VALUES (?1, ?2)
+                         ON CONFLICT (key) DO UPDATE SET value = ?2",
+                        (key, value),
+                    )?;
+                }
+                KvOp::Remove(key) => {
+                    txn.execute("DELETE FROM kv WHERE key = ?1", [key])?;
+                }
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn open_temp_tree() -> SqliteKvTree {
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("geph5-kv-test-{}-{id}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        SqliteKvTree::open(&path).unwrap()
+    }
+
+    #[test]
+    fn get_returns_none_for_missing_key() {
+        let tree = open_temp_tree();
+        assert_eq!(tree.get("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn insert_then_get_roundtrips() {
+        let tree = open_temp_tree();
+        tree.insert("k", b"v1").unwrap();
+        assert_eq!(tree.get("k").unwrap(), Some(b"v1".to_vec()));
+
+        tree.insert("k", b"v2").unwrap();
+        assert_eq!(tree.get("k").unwrap(), Some(b"v2".to_vec()));
+    }
+
+    #[test]
+    fn remove_deletes_key() {
+        let tree = open_temp_tree();
+        tree.insert("k", b"v").unwrap();
+        tree.remove("k").unwrap();
+        assert_eq!(tree.get("k").unwrap(), None);
+    }
+
+    #[test]
+    fn keys_lists_everything_stored() {
+        let tree = open_temp_tree();
+        tree.insert("a", b"1").unwrap();
+        tree.insert("b", b"2").unwrap();
+        let mut keys = tree.keys().unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn apply_commits_every_op_atomically() {
+        let tree = open_temp_tree();
+        tree.insert("stale", b"x").unwrap();
+        tree.apply(&[
+            KvOp::Insert("a".into(), b"1".to_vec()),
+            KvOp::Insert("b".into(), b"2".to_vec()),
+            KvOp::Remove("stale".into()),
+        ])
+        .unwrap();
+
+        assert_eq!(tree.get("a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(tree.get("b").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(tree.get("stale").unwrap(), None);
+    }
+}
@@ -1,34 +1,75 @@
-use std::path::PathBuf;
-
 use anyhow::Context as _;
 use moka::sync::Cache;
 use once_cell::sync::Lazy;
 use smol_str::SmolStr;
 
-static PREF_DIR: Lazy<PathBuf> = Lazy::new(|| {
-    let dir = dirs::config_dir()
-        .context("no config dir")
-        .unwrap()
-        .join("geph5-prefs");
+use crate::kv::{KvOp, KvTree, SqliteKvTree};
+
+static PREF_TREE: Lazy<SqliteKvTree> = Lazy::new(|| {
+    let dir = dirs::config_dir().context("no config dir").unwrap();
     std::fs::create_dir_all(&dir).unwrap();
-    dir
+    SqliteKvTree::open(&dir.join("geph5-prefs.db")).unwrap()
 });
 
 static PREF_CACHE: Lazy<Cache<SmolStr, SmolStr>> = Lazy::new(|| Cache::new(10000));
 
 pub fn pref_write(key: &str, val: &str) -> anyhow::Result<()> {
-    PREF_CACHE.remove(key);
-    let key_path = PREF_DIR.join(key);
-    std::fs::write(key_path, val.as_bytes())?;
+    PREF_TREE.insert(key, val.as_bytes())?;
+    PREF_CACHE.insert(key.into(), val.into());
     Ok(())
 }
 
 pub fn pref_read(key: &str) -> anyhow::Result<SmolStr> {
     PREF_CACHE
         .try_get_with(key.into(), || {
-            let key_path = PREF_DIR.join(key);
-            let contents = std::fs::read_to_string(key_path)?;
-            anyhow::Ok(SmolStr::from(contents))
+            let value = PREF_TREE
+                .get(key)?
+                .context("no such pref key")?;
+            anyhow::Ok(SmolStr::from(String::from_utf8(value)?))
         })
         .map_err(|e| anyhow::anyhow!("{e}"))
 }
+
+/// Removes a single preference, both from disk and from the in-memory
+/// cache.
+pub fn pref_remove(key: &str) -> anyhow::Result<()> {
+    PREF_TREE.remove(key)?;
+    PREF_CACHE.invalidate(key);
+    Ok(())
+}
+
+/// Lists every preference key currently stored, for settings export.
+pub fn pref_keys() -> anyhow::Result<Vec<SmolStr>> {
+    Ok(PREF_TREE
+        .keys()?
+        .into_iter()
+        .map(SmolStr::from)
+        .collect())
+}
+
+/// One step of an atomic, multi-key preferences update.
+pub enum PrefOp {
+    Write(SmolStr, SmolStr),
+    Remove(SmolStr),
+}
+
+/// Applies several preference writes/removals to disk as a single atomic
+/// transaction, so related keys are never stored half-updated. The
+/// in-memory cache is updated key by key after the transaction commits.
+pub fn pref_transaction(ops: Vec<PrefOp>) -> anyhow::Result<()> {
+    let kv_ops: Vec<KvOp> = ops
+        .iter()
+        .map(|op| match op {
+            PrefOp::Write(key, val) => KvOp::Insert(key.to_string(), val.as_bytes().to_vec()),
+            PrefOp::Remove(key) => KvOp::Remove(key.to_string()),
+        })
+        .collect();
+    PREF_TREE.apply(&kv_ops)?;
+    for op in ops {
+        match op {
+            PrefOp::Write(key, val) => PREF_CACHE.insert(key, val),
+            PrefOp::Remove(key) => PREF_CACHE.invalidate(&key),
+        }
+    }
+    Ok(())
+}
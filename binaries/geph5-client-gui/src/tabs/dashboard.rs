@@ -1,7 +1,8 @@
 use std::time::{Duration, Instant};
 
-use egui_plot::{Line, Plot, PlotPoints};
+use egui_plot::{Bar, BarChart, Line, Plot, PlotPoints, VLine};
 use geph5_client::ConnInfo;
+use geph5_histogram::LatencyHistogram;
 use once_cell::sync::Lazy;
 
 use crate::{
@@ -117,6 +118,43 @@ impl Dashboard {
             .show_axes(egui::Vec2b { x: false, y: true })
             .show(ui, |plot| plot.line(line));
 
+        ui.add_space(10.);
+        ui.label(l10n("latency"));
+
+        if let Some(ConnInfo::Connected(info)) = &conn_info {
+            let histogram = LatencyHistogram::from_samples(&info.recent_latencies_ms);
+            let bars: Vec<Bar> = histogram
+                .bars()
+                .into_iter()
+                .map(|(lower_bound_ms, count)| {
+                    Bar::new(lower_bound_ms, count as f64).fill(egui::Color32::LIGHT_BLUE)
+                })
+                .collect();
+
+            Plot::new("latency_plot")
+                .allow_drag(false)
+                .allow_zoom(false)
+                .allow_scroll(false)
+                .allow_boxed_zoom(false)
+                .y_axis_position(egui_plot::HPlacement::Right)
+                .y_axis_width(2)
+                .y_axis_label("samples")
+                .x_axis_label("ms")
+                .include_y(0.0)
+                .show(ui, |plot| {
+                    plot.bar_chart(BarChart::new(bars));
+                    for (label, p, color) in [
+                        ("p50", histogram.percentile(0.5), egui::Color32::GREEN),
+                        ("p90", histogram.percentile(0.9), egui::Color32::YELLOW),
+                        ("p99", histogram.percentile(0.99), egui::Color32::RED),
+                    ] {
+                        if let Some(p) = p {
+                            plot.vline(VLine::new(p).color(color).name(label));
+                        }
+                    }
+                });
+        }
+
         Ok(())
     }
 }
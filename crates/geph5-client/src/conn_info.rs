@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// The client's current connection state, as reported to the GUI over the
+/// daemon's control RPC.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ConnInfo {
+    Connecting,
+    Connected(ConnectedInfo),
+}
+
+/// Everything the GUI shows about a live connection.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ConnectedInfo {
+    /// Round-trip times, in milliseconds, sampled from recent route
+    /// probes -- feeds the Dashboard's latency-distribution panel.
+    pub recent_latencies_ms: Vec<f64>,
+}
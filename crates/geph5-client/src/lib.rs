@@ -0,0 +1,3 @@
+mod conn_info;
+
+pub use conn_info::{ConnInfo, ConnectedInfo};
@@ -0,0 +1,156 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Sub-buckets per octave: each doubling of latency (in ms) is split
+/// linearly into this many slots, so resolution stays reasonable without
+/// the bucket count blowing up.
+const SUBBUCKETS: usize = 4;
+/// Number of octaves tracked: buckets span 1 ms up to 2^20 ms (well past any
+/// route we'd still race); slower samples land in the last bucket.
+const MAX_OCTAVE: usize = 20;
+const NUM_BUCKETS: usize = MAX_OCTAVE * SUBBUCKETS;
+
+/// A log-linear histogram of round-trip-time samples, in milliseconds.
+/// Bucket boundaries double every `SUBBUCKETS` slots, so the whole thing is
+/// a fixed-size array of counters that's cheap to snapshot and merge.
+/// Shared between the broker (which records concurrently from many async
+/// tasks) and the client GUI (which rebuilds one from a batch of samples
+/// to feed the Dashboard's latency-distribution panel).
+pub struct LatencyHistogram {
+    counts: [AtomicU64; NUM_BUCKETS],
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Builds a histogram from a batch of already-collected samples.
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let hist = Self::default();
+        for &sample in samples {
+            hist.record(sample);
+        }
+        hist
+    }
+
+    fn bucket_of(latency_ms: f64) -> usize {
+        if latency_ms < 1.0 {
+            return 0;
+        }
+        let octave = latency_ms.log2().floor() as usize;
+        let octave_base = (1u64 << octave) as f64;
+        let within = ((latency_ms / octave_base - 1.0) * SUBBUCKETS as f64) as usize;
+        (octave * SUBBUCKETS + within.min(SUBBUCKETS - 1)).min(NUM_BUCKETS - 1)
+    }
+
+    fn bucket_lower_bound_ms(bucket: usize) -> f64 {
+        let octave = bucket / SUBBUCKETS;
+        let within = bucket % SUBBUCKETS;
+        (1u64 << octave) as f64 * (1.0 + within as f64 / SUBBUCKETS as f64)
+    }
+
+    pub fn record(&self, latency_ms: f64) {
+        self.counts[Self::bucket_of(latency_ms)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the lower bound of the bucket that holds the given
+    /// percentile (0.0..=1.0), or `None` if no samples were recorded.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        let total: u64 = self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum();
+        if total == 0 {
+            return None;
+        }
+        let target = ((total as f64) * p).ceil().max(1.0) as u64;
+        let mut cumulative = 0u64;
+        for (i, c) in self.counts.iter().enumerate() {
+            cumulative += c.load(Ordering::Relaxed);
+            if cumulative >= target {
+                return Some(Self::bucket_lower_bound_ms(i));
+            }
+        }
+        None
+    }
+
+    /// Non-empty `(lower_bound_ms, count)` bars, for an `egui_plot::BarChart`.
+    pub fn bars(&self) -> Vec<(f64, u64)> {
+        self.counts
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .map(|(i, count)| (Self::bucket_lower_bound_ms(i), count))
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Vec<u64> {
+        self.counts
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    pub fn merge(&self, other: &LatencyHistogram) {
+        for (mine, theirs) in self.counts.iter().zip(other.counts.iter()) {
+            mine.fetch_add(theirs.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_roundtrips_near_its_lower_bound() {
+        for bucket in 0..NUM_BUCKETS {
+            let lower = LatencyHistogram::bucket_lower_bound_ms(bucket);
+            if lower < 1.0 {
+                continue;
+            }
+            assert_eq!(LatencyHistogram::bucket_of(lower), bucket);
+        }
+    }
+
+    #[test]
+    fn percentile_is_none_when_empty() {
+        let hist = LatencyHistogram::default();
+        assert_eq!(hist.percentile(0.5), None);
+    }
+
+    #[test]
+    fn percentile_tracks_recorded_samples() {
+        let hist = LatencyHistogram::default();
+        for _ in 0..90 {
+            hist.record(10.0);
+        }
+        for _ in 0..10 {
+            hist.record(1000.0);
+        }
+        let p50 = hist.percentile(0.5).unwrap();
+        let p90 = hist.percentile(0.9).unwrap();
+        assert!(p50 < 100.0, "p50 should fall in the fast bucket: {p50}");
+        assert!(p90 >= p50, "p90 must not be below p50: {p90} < {p50}");
+    }
+
+    #[test]
+    fn merge_combines_counts() {
+        let a = LatencyHistogram::default();
+        let b = LatencyHistogram::default();
+        a.record(10.0);
+        b.record(10.0);
+        a.merge(&b);
+        assert_eq!(a.snapshot().iter().sum::<u64>(), 2);
+    }
+
+    #[test]
+    fn bars_skip_empty_buckets() {
+        let hist = LatencyHistogram::from_samples(&[10.0, 10.0, 2000.0]);
+        let bars = hist.bars();
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars.iter().map(|(_, count)| count).sum::<u64>(), 3);
+    }
+}